@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::graph::{NodeID, NodeRef};
+use crate::graph::{node_map::NodeMap, NodeID, NodeRef};
 
-use super::state::State;
+use super::{state::State, strategy::AutodiffCheckpointingStrategy};
 
 /// Definition of the forward function of a node, called during retropropagation only.
 /// This is different from the normal forward function because it reads and writes from
@@ -33,9 +33,13 @@ impl RetroForwards {
 }
 
 #[derive(new, Default)]
-/// Links [NodeID]s to their current [State]
+/// Links [NodeID]s to their current [State].
+///
+/// Backed by [NodeMap] rather than a bare `HashMap` so that, when a [Checkpoint] is shared
+/// across graphs merged from multiple losses, visiting this map always happens in [NodeID]
+/// order instead of following hash-bucket order.
 pub(crate) struct InnerStates {
-    map: HashMap<NodeID, State>,
+    map: NodeMap<State>,
 }
 
 impl InnerStates {
@@ -130,6 +134,28 @@ impl Checkpoint {
         self.inner_states.get_owned_and_downcasted::<T>(&node_id)
     }
 
+    /// Forces every node an [AutodiffCheckpointingStrategy] selects for `root` to
+    /// [State::Computed], instead of leaving it as [State::Recompute] until some descendant
+    /// fetches it through [Self::get].
+    ///
+    /// This runs the same ancestor walk as [Self::get] for each selected node, but stops short of
+    /// downcasting and removing it: once a [RetroForward] runs on a [State::Recompute] node it
+    /// overwrites that node's entry with [State::Computed] (see [RetroForwards::forward]), and
+    /// [Self::topological_sort] already stops descending into a node's parents once it finds it
+    /// [State::Computed]. So after this call, fetching anything downstream of a checkpointed node
+    /// recomputes only as far back as that checkpoint instead of all the way to the leaves.
+    pub fn checkpoint_with_strategy(
+        &mut self,
+        strategy: &dyn AutodiffCheckpointingStrategy,
+        root: NodeID,
+    ) {
+        for node_id in strategy.checkpoints(&self.node_tree, root) {
+            self.topological_sort(node_id)
+                .iter()
+                .for_each(|node| self.retro_forwards.forward(node, &mut self.inner_states));
+        }
+    }
+
     /// Insert a [State::Precomputed] at [NodeID]
     /// This is the actual checkpointing
     pub fn insert_pre_computed(&mut self, node_id: NodeID, state: State) {
@@ -144,29 +170,189 @@ impl Checkpoint {
         }
     }
 
-    /// Sorts the ancestors of NodeID in a way such that all parents come before their children
-    /// Useful to avoid recursivity later when mutating the states
+    /// Sorts the ancestors of NodeID in a way such that all parents come before their children.
+    /// Useful to avoid recursivity later when mutating the states.
+    ///
+    /// Uses an explicit stack instead of recursion so that deep recompute chains (e.g. unrolled
+    /// RNNs or very deep residual stacks) don't overflow the call stack. Each node is pushed
+    /// first as a "discover" entry and, if it still needs recomputing, re-pushed as a "finish"
+    /// entry below its unvisited parents; popping a finish entry appends the node to the sorted
+    /// output. The result is a post-order of the ancestors in which each node appears once, after
+    /// all of its parents.
     fn topological_sort(&self, node_id: NodeID) -> Vec<NodeID> {
-        match self.inner_states.get_ref(&node_id) {
-            Some(state) =>
-            {
-                match state {
-                State::Recompute {
-                    n_required: _,
-                } => {
-                    let mut sorted = Vec::new();
-                    for parent_node in self.node_tree.parents(&node_id) {
-                        sorted.extend(self.topological_sort(parent_node));
+        enum Entry {
+            Discover(NodeID),
+            Finish(NodeID),
+        }
+
+        let mut sorted = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![Entry::Discover(node_id)];
+
+        while let Some(entry) = stack.pop() {
+            match entry {
+                Entry::Finish(node_id) => sorted.push(node_id),
+                Entry::Discover(node_id) => {
+                    if !visited.insert(node_id.clone()) {
+                        continue;
+                    }
+
+                    match self.inner_states.get_ref(&node_id) {
+                        Some(State::Recompute { n_required: _ }) => {
+                            stack.push(Entry::Finish(node_id.clone()));
+                            for parent_node in self.node_tree.parents(&node_id) {
+                                if !visited.contains(&parent_node) {
+                                    stack.push(Entry::Discover(parent_node));
+                                }
+                            }
+                        }
+                        Some(State::Computed {
+                            state_content: _,
+                            n_required: _,
+                        }) => sorted.push(node_id),
+                        None => panic!(
+                            "Node is not in the map. You may have tried to access it more times than n_required allowed."
+                        ),
                     }
-                    sorted.push(node_id);
-                    sorted
                 }
+            }
+        }
+
+        sorted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{checkpoint::strategy::DominatorCheckpointingStrategy, graph::testing::test_node};
+
+    /// A [RetroForward] that counts how many times it actually ran, and forces its own node to
+    /// [State::Computed] when it does — mirroring what happens to a real node's state once it's
+    /// been recomputed.
+    struct CountingRetroForward {
+        node_id: NodeID,
+        call_count: Rc<Cell<usize>>,
+    }
+
+    impl RetroForward for CountingRetroForward {
+        fn forward(&self, states: &mut InnerStates) {
+            self.call_count.set(self.call_count.get() + 1);
+            states.insert(
+                self.node_id.clone(),
                 State::Computed {
-                    state_content: _,
-                    n_required: _,
-                } => vec![node_id],
-            }}
-            None => panic!("Node is not in the map. You may have tried to access it more times than n_required allowed.")
+                    state_content: Box::new(0i32),
+                    n_required: 1,
+                },
+            );
         }
     }
+
+    #[test]
+    fn topological_sort_orders_parents_before_children() {
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (mid_id, mid_ref) = test_node(vec![leaf_id.clone()]);
+        let (root_id, root_ref) = test_node(vec![mid_id.clone()]);
+
+        let mut inner_states = InnerStates::default();
+        inner_states.insert(
+            leaf_id.clone(),
+            State::Computed {
+                state_content: Box::new(0i32),
+                n_required: 1,
+            },
+        );
+        inner_states.insert(mid_id.clone(), State::Recompute { n_required: 1 });
+        inner_states.insert(root_id.clone(), State::Recompute { n_required: 1 });
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref);
+        node_tree.insert(mid_id.clone(), mid_ref);
+        node_tree.insert(root_id.clone(), root_ref);
+
+        let checkpoint = Checkpoint::new(inner_states, RetroForwards::default(), node_tree);
+
+        let sorted = checkpoint.topological_sort(root_id.clone());
+
+        let position = |node_id: &NodeID| sorted.iter().position(|n| n == node_id).unwrap();
+        assert!(position(&leaf_id) < position(&mid_id));
+        assert!(position(&mid_id) < position(&root_id));
+    }
+
+    #[test]
+    fn checkpoint_with_strategy_short_circuits_later_recomputation() {
+        // leaf -- mid -- root, a single chain so a memory budget of 1 checkpoints `mid`.
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (mid_id, mid_ref) = test_node(vec![leaf_id.clone()]);
+        let (root_id, root_ref) = test_node(vec![mid_id.clone()]);
+
+        let mut inner_states = InnerStates::default();
+        inner_states.insert(leaf_id.clone(), State::Recompute { n_required: 1 });
+        inner_states.insert(mid_id.clone(), State::Recompute { n_required: 1 });
+        inner_states.insert(root_id.clone(), State::Recompute { n_required: 1 });
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref);
+        node_tree.insert(mid_id.clone(), mid_ref);
+        node_tree.insert(root_id.clone(), root_ref);
+
+        let leaf_calls = Rc::new(Cell::new(0));
+        let mid_calls = Rc::new(Cell::new(0));
+        let root_calls = Rc::new(Cell::new(0));
+
+        let mut retro_forwards = RetroForwards::default();
+        retro_forwards.insert(
+            leaf_id.clone(),
+            Box::new(CountingRetroForward {
+                node_id: leaf_id.clone(),
+                call_count: leaf_calls.clone(),
+            }),
+        );
+        retro_forwards.insert(
+            mid_id.clone(),
+            Box::new(CountingRetroForward {
+                node_id: mid_id.clone(),
+                call_count: mid_calls.clone(),
+            }),
+        );
+        retro_forwards.insert(
+            root_id.clone(),
+            Box::new(CountingRetroForward {
+                node_id: root_id.clone(),
+                call_count: root_calls.clone(),
+            }),
+        );
+
+        let mut checkpoint = Checkpoint::new(inner_states, retro_forwards, node_tree);
+        let strategy = DominatorCheckpointingStrategy::new(1);
+
+        checkpoint.checkpoint_with_strategy(&strategy, root_id.clone());
+        assert_eq!(
+            leaf_calls.get(),
+            1,
+            "mid's ancestor had to recompute once to produce mid's checkpoint"
+        );
+        assert_eq!(
+            mid_calls.get(),
+            1,
+            "mid is the selected checkpoint, so it must have been forced to Computed"
+        );
+        assert_eq!(root_calls.get(), 0, "root was never asked for yet");
+
+        let _: i32 = checkpoint.get(root_id.clone());
+
+        assert_eq!(
+            leaf_calls.get(),
+            1,
+            "leaf must not recompute again: mid is already Computed, so fetching root stops there"
+        );
+        assert_eq!(
+            mid_calls.get(),
+            1,
+            "mid was already Computed, so its RetroForward is a no-op"
+        );
+        assert_eq!(root_calls.get(), 1, "root still had to recompute itself once");
+    }
 }
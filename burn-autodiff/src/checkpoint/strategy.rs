@@ -0,0 +1,335 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::NodeID;
+
+use super::base::NodeTree;
+
+/// Decides which nodes of the autodiff graph get checkpointed (forced to
+/// [State::Computed](super::state::State::Computed)) instead of being recomputed on demand
+/// during the backward pass.
+///
+/// Named `Autodiff*` to avoid colliding with the unrelated model/optimizer
+/// `CheckpointingStrategy` in `burn-train`.
+pub(crate) trait AutodiffCheckpointingStrategy: Send + Sync + std::fmt::Debug {
+    /// Returns the set of nodes to checkpoint for the backward pass rooted at `root`.
+    fn checkpoints(&self, node_tree: &NodeTree, root: NodeID) -> HashSet<NodeID>;
+}
+
+/// Automatically places checkpoints at the immediate dominators of `root`, instead of relying on
+/// manually annotated [State::Computed](super::state::State::Computed) nodes.
+///
+/// A node `d` dominates `root` if every recompute path from a leaf to `root` passes through `d`,
+/// so checkpointing the dominators splits the graph into segments that each recompute
+/// independently between two consecutive checkpoints. `memory_budget` bounds how many of those
+/// dominators are kept in total, split evenly across every leaf-to-root chain (not just the
+/// longest one) so a multi-head or residual graph gets its memory bounded on every branch.
+#[derive(new, Debug)]
+pub(crate) struct DominatorCheckpointingStrategy {
+    /// Maximum number of nodes this strategy will checkpoint.
+    memory_budget: usize,
+}
+
+impl AutodiffCheckpointingStrategy for DominatorCheckpointingStrategy {
+    fn checkpoints(&self, node_tree: &NodeTree, root: NodeID) -> HashSet<NodeID> {
+        let rpo = reverse_postorder(node_tree, &root);
+        let idom = immediate_dominators(node_tree, &root, &rpo);
+
+        select_checkpoints(&root, &idom, node_tree, &rpo, self.memory_budget)
+    }
+}
+
+/// Reverse post-order numbering of the nodes reachable from `root`, following [NodeTree] parent
+/// edges as the successor relation. Computed with an explicit stack, mirroring
+/// [Checkpoint::topological_sort](super::base::Checkpoint::topological_sort), so deep graphs
+/// don't overflow the call stack.
+fn reverse_postorder(node_tree: &NodeTree, root: &NodeID) -> Vec<NodeID> {
+    enum Entry {
+        Discover(NodeID),
+        Finish(NodeID),
+    }
+
+    let mut visited = HashSet::new();
+    let mut postorder = Vec::new();
+    let mut stack = vec![Entry::Discover(root.clone())];
+
+    while let Some(entry) = stack.pop() {
+        match entry {
+            Entry::Finish(node_id) => postorder.push(node_id),
+            Entry::Discover(node_id) => {
+                if !visited.insert(node_id.clone()) {
+                    continue;
+                }
+
+                stack.push(Entry::Finish(node_id.clone()));
+                for parent_node in node_tree.parents(&node_id) {
+                    if !visited.contains(&parent_node) {
+                        stack.push(Entry::Discover(parent_node));
+                    }
+                }
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+/// Computes the immediate dominator of every node reachable from `root`, using the
+/// Cooper-Harvey-Kennedy fixed-point algorithm: repeatedly intersect the dominators of a node's
+/// already-processed predecessors, walking the higher reverse-post-order-numbered side of the
+/// `idom` chain up until the two meet, until no `idom` changes.
+fn immediate_dominators(
+    node_tree: &NodeTree,
+    root: &NodeID,
+    rpo: &[NodeID],
+) -> HashMap<NodeID, NodeID> {
+    let rpo_number: HashMap<NodeID, usize> = rpo
+        .iter()
+        .enumerate()
+        .map(|(index, node_id)| (node_id.clone(), index))
+        .collect();
+
+    // Predecessors in the dominator sense: `p` is a predecessor of `n` when `n` is one of `p`'s
+    // parents, i.e. the walk from `root` towards its parents reaches `n` via `p`.
+    let mut predecessors: HashMap<NodeID, Vec<NodeID>> = HashMap::new();
+    for node_id in rpo {
+        for parent_node in node_tree.parents(node_id) {
+            if rpo_number.contains_key(&parent_node) {
+                predecessors
+                    .entry(parent_node)
+                    .or_default()
+                    .push(node_id.clone());
+            }
+        }
+    }
+
+    let mut idom: HashMap<NodeID, NodeID> = HashMap::new();
+    idom.insert(root.clone(), root.clone());
+
+    let intersect = |idom: &HashMap<NodeID, NodeID>, mut a: NodeID, mut b: NodeID| -> NodeID {
+        while a != b {
+            while rpo_number[&a] > rpo_number[&b] {
+                a = idom[&a].clone();
+            }
+            while rpo_number[&b] > rpo_number[&a] {
+                b = idom[&b].clone();
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for node_id in rpo.iter().skip(1) {
+            let mut new_idom = None;
+            for pred in predecessors.get(node_id).into_iter().flatten() {
+                if !idom.contains_key(pred) {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => pred.clone(),
+                    Some(current) => intersect(&idom, pred.clone(), current),
+                });
+            }
+
+            if let Some(new_idom) = new_idom {
+                if idom.get(node_id) != Some(&new_idom) {
+                    idom.insert(node_id.clone(), new_idom);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    idom
+}
+
+/// For every leaf (a reachable node with no parents of its own), walks its `idom` chain back to
+/// `root` and keeps an evenly spread share of `memory_budget` along that chain. The shares are
+/// allocated by [allocate_branch_budgets] so they always sum to at most `memory_budget`: once
+/// there are more branches than the budget allows, some branches get zero checkpoints rather
+/// than every branch getting a floor of one, which would let the total grow with the number of
+/// branches instead of staying capped at `memory_budget`.
+fn select_checkpoints(
+    root: &NodeID,
+    idom: &HashMap<NodeID, NodeID>,
+    node_tree: &NodeTree,
+    rpo: &[NodeID],
+    memory_budget: usize,
+) -> HashSet<NodeID> {
+    if memory_budget == 0 {
+        return HashSet::new();
+    }
+
+    let leaves: Vec<NodeID> = rpo
+        .iter()
+        .filter(|node_id| node_tree.parents(node_id).is_empty())
+        .cloned()
+        .collect();
+
+    if leaves.is_empty() {
+        return HashSet::new();
+    }
+
+    let chains: Vec<Vec<NodeID>> = leaves.iter().map(|leaf| idom_chain(leaf, root, idom)).collect();
+    let branch_budgets = allocate_branch_budgets(&chains, memory_budget);
+
+    let mut checkpoints = HashSet::new();
+    for (chain, branch_budget) in chains.iter().zip(branch_budgets) {
+        if chain.is_empty() || branch_budget == 0 {
+            continue;
+        }
+
+        if chain.len() <= branch_budget {
+            checkpoints.extend(chain.iter().cloned());
+            continue;
+        }
+
+        let stride = chain.len() as f64 / branch_budget as f64;
+        for i in 0..branch_budget {
+            let index = ((i as f64 * stride) as usize).min(chain.len() - 1);
+            checkpoints.insert(chain[index].clone());
+        }
+    }
+
+    checkpoints
+}
+
+/// Splits `total_budget` checkpoints across `chains` so the shares always sum to at most
+/// `total_budget`, instead of flooring every chain to at least one (which would make the total
+/// grow with the number of chains). The chains with the most nodes to bound get the extra share
+/// when `total_budget` doesn't divide evenly.
+fn allocate_branch_budgets(chains: &[Vec<NodeID>], total_budget: usize) -> Vec<usize> {
+    let base_share = total_budget / chains.len();
+    let extra_shares = total_budget % chains.len();
+
+    let mut longest_first: Vec<usize> = (0..chains.len()).collect();
+    longest_first.sort_by_key(|&index| std::cmp::Reverse(chains[index].len()));
+
+    let mut budgets = vec![base_share; chains.len()];
+    for &index in longest_first.iter().take(extra_shares) {
+        budgets[index] += 1;
+    }
+
+    budgets
+}
+
+/// The chain of nodes from `leaf` up to (excluding) `root`, following `idom`, in root-to-leaf
+/// order.
+fn idom_chain(leaf: &NodeID, root: &NodeID, idom: &HashMap<NodeID, NodeID>) -> Vec<NodeID> {
+    let mut chain = Vec::new();
+    let mut current = leaf.clone();
+    while &current != root {
+        chain.push(current.clone());
+        current = match idom.get(&current) {
+            Some(parent) => parent.clone(),
+            None => break,
+        };
+    }
+    chain.reverse();
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{testing::test_node, NodeRef};
+
+    /// Two branches merging at `root`:
+    /// ```text
+    /// leaf_a -- a --\
+    ///                root
+    /// leaf_b -- b --/
+    /// ```
+    fn two_branch_graph() -> (NodeTree, NodeID, NodeID, NodeID, NodeID, NodeID) {
+        let (leaf_a_id, leaf_a_ref) = test_node(vec![]);
+        let (a_id, a_ref) = test_node(vec![leaf_a_id.clone()]);
+        let (leaf_b_id, leaf_b_ref) = test_node(vec![]);
+        let (b_id, b_ref) = test_node(vec![leaf_b_id.clone()]);
+        let (root_id, root_ref) = test_node(vec![a_id.clone(), b_id.clone()]);
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_a_id.clone(), leaf_a_ref);
+        node_tree.insert(a_id.clone(), a_ref);
+        node_tree.insert(leaf_b_id.clone(), leaf_b_ref);
+        node_tree.insert(b_id.clone(), b_ref);
+        node_tree.insert(root_id.clone(), root_ref);
+
+        (node_tree, root_id, a_id, leaf_a_id, b_id, leaf_b_id)
+    }
+
+    #[test]
+    fn immediate_dominators_agree_with_each_branch_separately() {
+        let (node_tree, root_id, a_id, leaf_a_id, b_id, leaf_b_id) = two_branch_graph();
+
+        let rpo = reverse_postorder(&node_tree, &root_id);
+        let idom = immediate_dominators(&node_tree, &root_id, &rpo);
+
+        assert_eq!(idom[&a_id], root_id);
+        assert_eq!(idom[&b_id], root_id);
+        assert_eq!(idom[&leaf_a_id], a_id);
+        assert_eq!(idom[&leaf_b_id], b_id);
+    }
+
+    #[test]
+    fn select_checkpoints_covers_every_branch_instead_of_one_arbitrary_leaf() {
+        let (node_tree, root_id, a_id, _leaf_a_id, b_id, _leaf_b_id) = two_branch_graph();
+
+        let rpo = reverse_postorder(&node_tree, &root_id);
+        let idom = immediate_dominators(&node_tree, &root_id, &rpo);
+
+        // A budget of 2 should place exactly one checkpoint per branch, not spend both on
+        // whichever leaf a single DFS path happened to visit last.
+        let checkpoints = select_checkpoints(&root_id, &idom, &node_tree, &rpo, 2);
+
+        assert!(checkpoints.contains(&a_id));
+        assert!(checkpoints.contains(&b_id));
+        assert_eq!(checkpoints.len(), 2);
+    }
+
+    /// `branch_count` leaves, each a direct parent of `root`, so every idom chain has length 1.
+    fn n_branch_graph(branch_count: usize) -> (NodeTree, NodeID, Vec<NodeID>) {
+        let branches: Vec<(NodeID, NodeRef)> = (0..branch_count).map(|_| test_node(vec![])).collect();
+        let branch_ids: Vec<NodeID> = branches.iter().map(|(id, _)| id.clone()).collect();
+        let (root_id, root_ref) = test_node(branch_ids.clone());
+
+        let mut node_tree = NodeTree::default();
+        for (id, node_ref) in branches {
+            node_tree.insert(id, node_ref);
+        }
+        node_tree.insert(root_id.clone(), root_ref);
+
+        (node_tree, root_id, branch_ids)
+    }
+
+    #[test]
+    fn select_checkpoints_never_exceeds_memory_budget_with_more_branches_than_budget() {
+        let (node_tree, root_id, _branch_ids) = n_branch_graph(3);
+        let rpo = reverse_postorder(&node_tree, &root_id);
+        let idom = immediate_dominators(&node_tree, &root_id, &rpo);
+
+        // 3 branches, budget 1: must checkpoint exactly 1 node total, not 1 per branch.
+        let checkpoints = select_checkpoints(&root_id, &idom, &node_tree, &rpo, 1);
+        assert_eq!(checkpoints.len(), 1);
+
+        let (node_tree, root_id, _branch_ids) = n_branch_graph(5);
+        let rpo = reverse_postorder(&node_tree, &root_id);
+        let idom = immediate_dominators(&node_tree, &root_id, &rpo);
+
+        // 5 branches, budget 2: must checkpoint exactly 2 nodes total, not 1 per branch.
+        let checkpoints = select_checkpoints(&root_id, &idom, &node_tree, &rpo, 2);
+        assert_eq!(checkpoints.len(), 2);
+    }
+
+    #[test]
+    fn select_checkpoints_with_zero_budget_checkpoints_nothing() {
+        let (node_tree, root_id, ..) = two_branch_graph();
+
+        let rpo = reverse_postorder(&node_tree, &root_id);
+        let idom = immediate_dominators(&node_tree, &root_id, &rpo);
+
+        assert!(select_checkpoints(&root_id, &idom, &node_tree, &rpo, 0).is_empty());
+    }
+}
@@ -1,13 +1,16 @@
 use spin::Mutex;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashSet, sync::Arc};
 
 use crate::{
-    checkpoint::{base::Checkpointer, builder::build_checkpointer},
+    checkpoint::{
+        base::{Checkpointer, NodeTree},
+        builder::build_checkpointer,
+    },
     grads::Gradients,
     ops::CheckpointingAction,
 };
 
-use super::{NodeID, NodeRef};
+use super::{node_map::NodeMap, scheduler::WaveScheduler, NodeID, NodeRef};
 
 /// Backward step for reverse mode autodiff.
 pub trait Step: Send + Sync + std::fmt::Debug {
@@ -18,7 +21,9 @@ pub trait Step: Send + Sync + std::fmt::Debug {
 }
 
 pub type StepBoxed = Box<dyn Step>;
-pub type NodeSteps = HashMap<NodeID, StepBoxed>;
+/// Registered [steps](Step) keyed by [NodeID]. Iterating it (e.g. through [Graph::merge]) always
+/// visits nodes in [NodeID] order, see [NodeMap], so gradient accumulation stays deterministic.
+pub type NodeSteps = NodeMap<StepBoxed>;
 
 #[derive(new, Debug, Default)]
 pub struct CheckpointingActions {
@@ -66,13 +71,48 @@ impl Graph {
     /// This is useful, since the graph is supposed to be consumed only once for backprop, and
     /// keeping all the tensors alive for multiple backward call is a heavy waste of resources.
     pub fn steps(self) -> NodeSteps {
-        let mut map_drain = HashMap::new();
+        let mut map_drain = NodeMap::new();
         self.execute_mut_steps(|map| {
             std::mem::swap(&mut *map, &mut map_drain);
         });
         map_drain
     }
 
+    /// Drops steps, and their corresponding checkpointing actions, that can't reach `root`
+    /// through [NodeTree] parent edges.
+    ///
+    /// In a typical training step, many registered nodes never contribute a gradient to the
+    /// final loss: detached branches, unused heads, metrics computed alongside the loss. Pruning
+    /// them before backprop frees their retained tensors early and shrinks the backward pass.
+    ///
+    /// This is opt-in rather than automatic in [Self::steps], since a graph merged from multiple
+    /// losses may still need steps that are unreachable from any single `root`.
+    pub fn prune_unreachable(self, root: &NodeID, node_tree: &NodeTree) -> Self {
+        let reachable = reachable_nodes(root, node_tree);
+
+        self.execute_mut_steps(|steps| {
+            steps.retain(|node_id, _| reachable.contains(node_id));
+        })
+        .execute_mut_checkpointing_actions(|actions| {
+            actions
+                .main_actions
+                .retain(|action| reachable.contains(&action.node_id()));
+            actions
+                .backup_actions
+                .retain(|action| reachable.contains(&action.node_id()));
+        })
+    }
+
+    /// Builds a [WaveScheduler] over this graph's steps, exposing independent waves of steps that
+    /// can be dispatched concurrently instead of one flat, serialized map.
+    ///
+    /// # Notes
+    ///
+    /// This is a owned method, for the same reason as [Self::steps].
+    pub fn waves(self, node_tree: &NodeTree) -> WaveScheduler {
+        WaveScheduler::new(self.steps(), node_tree)
+    }
+
     /// Register a new step into the graph.
     pub fn register(self, id: &NodeID, ops: StepBoxed) -> Self {
         self.execute_mut_steps(|map| {
@@ -113,7 +153,7 @@ impl Graph {
             if map1.len() > map2.len() {
                 map1.extend(map2);
             } else {
-                let mut map_drain = HashMap::new();
+                let mut map_drain = NodeMap::new();
                 std::mem::swap(map1, &mut map_drain);
                 map2.extend(map_drain);
                 std::mem::swap(map1, &mut map2);
@@ -174,3 +214,94 @@ impl Graph {
             .extend(checkpointing_actions);
     }
 }
+
+/// Backward reachability walk from `root` over [NodeTree] parent edges, with an explicit stack
+/// so it doesn't recurse once per node.
+fn reachable_nodes(root: &NodeID, node_tree: &NodeTree) -> HashSet<NodeID> {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![root.clone()];
+
+    while let Some(node_id) = stack.pop() {
+        if !reachable.insert(node_id.clone()) {
+            continue;
+        }
+
+        for parent_node in node_tree.parents(&node_id) {
+            if !reachable.contains(&parent_node) {
+                stack.push(parent_node);
+            }
+        }
+    }
+
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::testing::test_node;
+
+    #[derive(Debug)]
+    struct NoopStep;
+
+    impl Step for NoopStep {
+        fn step(self: Box<Self>, _grads: &mut Gradients, _checkpointer: &mut Checkpointer) {
+            unreachable!("not exercised by pruning tests")
+        }
+
+        fn node(&self) -> NodeRef {
+            unreachable!("not exercised by pruning tests")
+        }
+    }
+
+    #[test]
+    fn prune_unreachable_drops_steps_that_cant_reach_root() {
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (used_id, used_ref) = test_node(vec![leaf_id.clone()]);
+        let (unused_id, unused_ref) = test_node(vec![leaf_id.clone()]);
+        let (root_id, root_ref) = test_node(vec![used_id.clone()]);
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref);
+        node_tree.insert(used_id.clone(), used_ref);
+        node_tree.insert(unused_id.clone(), unused_ref);
+        node_tree.insert(root_id.clone(), root_ref);
+
+        let graph = Graph::new()
+            .register(&leaf_id, Box::new(NoopStep))
+            .register(&used_id, Box::new(NoopStep))
+            .register(&unused_id, Box::new(NoopStep))
+            .prune_unreachable(&root_id, &node_tree);
+
+        let remaining: Vec<NodeID> = graph.steps().into_iter().map(|(id, _)| id).collect();
+
+        assert!(remaining.contains(&leaf_id));
+        assert!(remaining.contains(&used_id));
+        assert!(!remaining.contains(&unused_id));
+        assert_eq!(remaining.len(), 2);
+    }
+
+    #[test]
+    fn reachable_nodes_keeps_exactly_the_ancestors_of_root() {
+        // leaf -- used -- root
+        //      \- unused (not an ancestor of root: dropped)
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (used_id, used_ref) = test_node(vec![leaf_id.clone()]);
+        let (unused_id, unused_ref) = test_node(vec![leaf_id.clone()]);
+        let (root_id, root_ref) = test_node(vec![used_id.clone()]);
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref);
+        node_tree.insert(used_id.clone(), used_ref);
+        node_tree.insert(unused_id.clone(), unused_ref);
+        node_tree.insert(root_id.clone(), root_ref);
+
+        let reachable = reachable_nodes(&root_id, &node_tree);
+
+        assert!(reachable.contains(&root_id));
+        assert!(reachable.contains(&used_id));
+        assert!(reachable.contains(&leaf_id));
+        assert!(!reachable.contains(&unused_id));
+        assert_eq!(reachable.len(), 3);
+    }
+}
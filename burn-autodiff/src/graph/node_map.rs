@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use super::NodeID;
+
+/// A [HashMap] keyed by [NodeID] that never exposes hash-bucket iteration order: [Self::retain],
+/// [Self::extend] and every iteration method walk entries sorted by [NodeID] instead.
+///
+/// When gradients from multiple children are summed at a shared parent, or two graphs are merged,
+/// the float accumulation order must be the same on every run for bitwise-reproducible training,
+/// which a bare `HashMap`'s hash-iteration order can't guarantee. Sorting requires `NodeID: Ord`,
+/// which every caller here already assumes by storing it as the key of this map.
+#[derive(Debug)]
+pub struct NodeMap<V> {
+    map: HashMap<NodeID, V>,
+}
+
+impl<V> Default for NodeMap<V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl<V> NodeMap<V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value for `node_id`, returning the previous one if any.
+    pub fn insert(&mut self, node_id: NodeID, value: V) -> Option<V> {
+        self.map.insert(node_id, value)
+    }
+
+    /// Removes and returns the value for `node_id`, if present.
+    pub fn remove(&mut self, node_id: &NodeID) -> Option<V> {
+        self.map.remove(node_id)
+    }
+
+    /// Returns a reference to the value for `node_id`, if present.
+    pub fn get(&self, node_id: &NodeID) -> Option<&V> {
+        self.map.get(node_id)
+    }
+
+    /// Returns whether `node_id` has a value in the map.
+    pub fn contains_key(&self, node_id: &NodeID) -> bool {
+        self.map.contains_key(node_id)
+    }
+
+    /// Number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Keeps only the entries for which `f` returns `true`, visiting them in [NodeID] order.
+    pub fn retain<F: FnMut(&NodeID, &mut V) -> bool>(&mut self, mut f: F) {
+        for node_id in self.sorted_keys() {
+            let keep = match self.map.get_mut(&node_id) {
+                Some(value) => f(&node_id, value),
+                None => continue,
+            };
+            if !keep {
+                self.map.remove(&node_id);
+            }
+        }
+    }
+
+    /// Moves every entry of `other` into `self`, visiting `other` in [NodeID] order.
+    pub fn extend(&mut self, other: Self) {
+        for (node_id, value) in other.into_sorted_vec() {
+            self.map.insert(node_id, value);
+        }
+    }
+
+    /// All entries, sorted by [NodeID]. This is the only iteration order this type exposes.
+    pub fn into_sorted_vec(self) -> Vec<(NodeID, V)> {
+        let mut entries: Vec<_> = self.map.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries
+    }
+
+    /// Entries sorted by [NodeID], the only iteration order this type exposes.
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeID, &V)> {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries.into_iter()
+    }
+
+    /// All keys, sorted by [NodeID].
+    pub fn keys_sorted(&self) -> Vec<NodeID> {
+        self.sorted_keys()
+    }
+
+    fn sorted_keys(&self) -> Vec<NodeID> {
+        let mut keys: Vec<_> = self.map.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+}
+
+impl<V> IntoIterator for NodeMap<V> {
+    type Item = (NodeID, V);
+    type IntoIter = std::vec::IntoIter<(NodeID, V)>;
+
+    /// Consumes the map, yielding entries sorted by [NodeID] — the only order this type exposes,
+    /// so callers that used to iterate the old `HashMap<NodeID, _>` directly (e.g. the backward
+    /// pass driving `Graph::steps()`) get determinism without changing their loop.
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_sorted_vec().into_iter()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a NodeMap<V> {
+    type Item = (&'a NodeID, &'a V);
+    type IntoIter = std::vec::IntoIter<(&'a NodeID, &'a V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_in_node_id_order_regardless_of_insertion_order() {
+        let ids: Vec<NodeID> = (0..8).map(|_| NodeID::new()).collect();
+
+        let mut map = NodeMap::new();
+        // Insert in reverse order so hash-bucket order (if it were used) would very likely
+        // disagree with NodeID order.
+        for id in ids.iter().rev() {
+            map.insert(id.clone(), ());
+        }
+
+        let mut expected = ids.clone();
+        expected.sort();
+
+        let iterated: Vec<NodeID> = map.iter().map(|(id, _)| id.clone()).collect();
+        assert_eq!(iterated, expected);
+
+        let owned: Vec<NodeID> = map.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(owned, expected);
+    }
+
+    #[test]
+    fn extend_keeps_every_entry_sorted() {
+        let ids: Vec<NodeID> = (0..4).map(|_| NodeID::new()).collect();
+
+        let mut a = NodeMap::new();
+        a.insert(ids[2].clone(), "a2");
+        a.insert(ids[0].clone(), "a0");
+
+        let mut b = NodeMap::new();
+        b.insert(ids[3].clone(), "b3");
+        b.insert(ids[1].clone(), "b1");
+
+        a.extend(b);
+
+        let mut expected = ids.clone();
+        expected.sort();
+        let keys: Vec<NodeID> = a.keys_sorted();
+        assert_eq!(keys, expected);
+        assert_eq!(a.len(), 4);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeSet;
+
+use super::{node_map::NodeMap, NodeID, NodeSteps, StepBoxed};
+use crate::checkpoint::base::NodeTree;
+
+/// Splits [NodeSteps] into waves of steps that can run concurrently, instead of handing back a
+/// single flat map that serializes the whole backward pass.
+///
+/// Each node tracks how many of its children (the steps that read its gradient, found by walking
+/// [NodeTree] parent edges) still have to run. A wave is every step whose count has reached zero,
+/// except that a step sharing a parent with an earlier step of the wave waits for the next one;
+/// since none of those steps can depend on each other's output and they touch disjoint parents,
+/// they can be dispatched on separate threads or backend streams. [Self::complete] decrements the
+/// counters for the next wave, and [Self::next_wave] compacts finished entries out of the
+/// underlying map as it goes, so live memory only holds steps that are still pending.
+///
+/// The gradients produced are identical to running the steps one at a time in topological order;
+/// this only changes how much of that order is exposed as independent work.
+pub struct WaveScheduler {
+    steps: NodeSteps,
+    pending_children: NodeMap<usize>,
+    /// Nodes whose pending-children count has reached zero but haven't been dispatched yet,
+    /// kept sorted by [NodeID] incrementally so [Self::next_wave] never has to re-sort the
+    /// whole remaining key set.
+    ready: BTreeSet<NodeID>,
+    /// Number of nodes from the last [Self::next_wave] that haven't been passed to
+    /// [Self::complete] yet. [Self::next_wave] refuses to form a new wave while this is nonzero,
+    /// so forgetting to call [Self::complete] on every dispatched node panics instead of silently
+    /// returning empty waves forever.
+    in_flight: usize,
+}
+
+impl WaveScheduler {
+    /// Builds a scheduler for `steps`, counting each node's children among `steps` via
+    /// `node_tree`.
+    pub fn new(steps: NodeSteps, node_tree: &NodeTree) -> Self {
+        let node_ids = steps.keys_sorted();
+
+        let mut pending_children = NodeMap::new();
+        for node_id in &node_ids {
+            pending_children.insert(node_id.clone(), 0);
+        }
+        for node_id in &node_ids {
+            for parent_node in node_tree.parents(node_id) {
+                if let Some(count) = pending_children.get(&parent_node) {
+                    let count = *count;
+                    pending_children.insert(parent_node, count + 1);
+                }
+            }
+        }
+
+        let ready = node_ids
+            .iter()
+            .filter(|node_id| pending_children.get(node_id) == Some(&0))
+            .cloned()
+            .collect();
+
+        Self {
+            steps,
+            pending_children,
+            ready,
+            in_flight: 0,
+        }
+    }
+
+    /// Whether every step has been dispatched.
+    pub fn is_done(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Pops the next wave: every remaining step whose children have all completed, removed from
+    /// the underlying map, minus any step that shares a parent with an earlier step of the wave
+    /// (in [NodeID] order); those stay ready for a later wave. Returns `None` once nothing is
+    /// left to dispatch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called again while nodes from the previous wave haven't been passed to
+    /// [Self::complete] yet, since forming a wave without them would just return an empty one
+    /// forever instead of making progress.
+    pub fn next_wave(&mut self) -> Option<Vec<(NodeID, StepBoxed)>> {
+        assert_eq!(
+            self.in_flight, 0,
+            "WaveScheduler::next_wave called before complete() was called for every node of the previous wave"
+        );
+
+        if self.is_done() {
+            return None;
+        }
+
+        let ready = std::mem::take(&mut self.ready);
+
+        // A ready step that shares a parent with an earlier step of this wave stays ready for
+        // the next wave, so that the steps of one wave never write the same parent's gradient.
+        let mut claimed_parents = BTreeSet::new();
+        let mut wave = Vec::with_capacity(ready.len());
+        for node_id in ready {
+            if let Some(step) = self.steps.get(&node_id) {
+                let parents = step.node().parents.clone();
+                if parents.iter().any(|parent| claimed_parents.contains(parent)) {
+                    self.ready.insert(node_id);
+                    continue;
+                }
+                claimed_parents.extend(parents);
+            }
+            self.pending_children.remove(&node_id);
+            if let Some(step) = self.steps.remove(&node_id) {
+                wave.push((node_id, step));
+            }
+        }
+
+        self.in_flight = wave.len();
+        Some(wave)
+    }
+
+    /// Marks `node_id` as finished, decrementing the pending-children count of its parents so
+    /// they can be included in a future wave once all their children are done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called more times than [Self::next_wave] dispatched nodes for the current wave.
+    pub fn complete(&mut self, node_id: &NodeID, node_tree: &NodeTree) {
+        self.in_flight = self
+            .in_flight
+            .checked_sub(1)
+            .expect("WaveScheduler::complete called more times than the current wave dispatched");
+
+        for parent_node in node_tree.parents(node_id) {
+            if let Some(count) = self.pending_children.get(&parent_node) {
+                let remaining = count.saturating_sub(1);
+                self.pending_children.insert(parent_node.clone(), remaining);
+                if remaining == 0 {
+                    self.ready.insert(parent_node);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        checkpoint::base::Checkpointer,
+        grads::Gradients,
+        graph::{testing::test_node, NodeRef},
+    };
+
+    #[derive(Debug)]
+    struct NoopStep(NodeRef);
+
+    impl super::super::Step for NoopStep {
+        fn step(self: Box<Self>, _grads: &mut Gradients, _checkpointer: &mut Checkpointer) {
+            unreachable!("not exercised by the wave-ordering test")
+        }
+
+        fn node(&self) -> NodeRef {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn dispatches_waves_in_dependency_order_and_terminates() {
+        // `parent_id`'s parent is `leaf_id`, so nothing has `parent_id` as one of its own
+        // parents: it starts with zero pending children and ships first, same as a real
+        // backward pass processing the node nearest the root before its ancestors.
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (parent_id, parent_ref) = test_node(vec![leaf_id.clone()]);
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref.clone());
+        node_tree.insert(parent_id.clone(), parent_ref.clone());
+
+        let mut steps = NodeSteps::new();
+        steps.insert(leaf_id.clone(), Box::new(NoopStep(leaf_ref)));
+        steps.insert(parent_id.clone(), Box::new(NoopStep(parent_ref)));
+
+        let mut scheduler = WaveScheduler::new(steps, &node_tree);
+
+        let wave1 = scheduler.next_wave().expect("graph is not empty yet");
+        let wave1_ids: Vec<NodeID> = wave1.iter().map(|(id, _)| id.clone()).collect();
+        assert_eq!(wave1_ids, vec![parent_id.clone()]);
+
+        for (node_id, _) in wave1 {
+            scheduler.complete(&node_id, &node_tree);
+        }
+
+        let wave2 = scheduler.next_wave().expect("leaf still pending");
+        let wave2_ids: Vec<NodeID> = wave2.iter().map(|(id, _)| id.clone()).collect();
+        assert_eq!(wave2_ids, vec![leaf_id.clone()]);
+
+        for (node_id, _) in wave2 {
+            scheduler.complete(&node_id, &node_tree);
+        }
+
+        assert!(scheduler.is_done());
+        assert!(scheduler.next_wave().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "complete() was called for every node")]
+    fn next_wave_panics_if_previous_wave_was_not_completed() {
+        let (leaf_id, leaf_ref) = test_node(vec![]);
+        let (parent_id, parent_ref) = test_node(vec![leaf_id.clone()]);
+
+        let mut node_tree = NodeTree::default();
+        node_tree.insert(leaf_id.clone(), leaf_ref.clone());
+        node_tree.insert(parent_id.clone(), parent_ref.clone());
+
+        let mut steps = NodeSteps::new();
+        steps.insert(leaf_id, Box::new(NoopStep(leaf_ref)));
+        steps.insert(parent_id, Box::new(NoopStep(parent_ref)));
+
+        let mut scheduler = WaveScheduler::new(steps, &node_tree);
+        scheduler.next_wave();
+        // Missing the `complete()` calls for the dispatched wave.
+        scheduler.next_wave();
+    }
+}
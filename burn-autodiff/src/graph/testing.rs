@@ -0,0 +1,13 @@
+//! Test-only graph-construction helpers shared by the `graph` and `checkpoint` unit tests.
+#![cfg(test)]
+
+use std::sync::Arc;
+
+use super::{Node, NodeID, NodeRef};
+
+/// Builds a fresh [NodeID] and the [NodeRef] pointing at it with the given `parents`.
+pub(crate) fn test_node(parents: Vec<NodeID>) -> (NodeID, NodeRef) {
+    let id = NodeID::new();
+    let node_ref: NodeRef = Arc::new(Node::new(id.clone(), parents));
+    (id, node_ref)
+}